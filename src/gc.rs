@@ -1,16 +1,170 @@
 use std::{
-    alloc::{Alloc, Layout},
+    alloc::{Alloc, AllocErr, Layout},
     any::Any,
+    cell::UnsafeCell,
     marker::Unsize,
-    mem::{forget, transmute, ManuallyDrop},
-    ops::{CoerceUnsized, Deref, DerefMut},
-    ptr::NonNull,
+    mem::{forget, size_of, size_of_val, transmute, ManuallyDrop},
+    ops::{CoerceUnsized, Deref, DerefMut, Index, IndexMut},
+    ptr::{self, NonNull},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
 };
 
 use crate::allocator::{BlockHeader, BlockMetadata};
 
 use crate::GC_ALLOCATOR;
 
+/// Total bytes currently live in `Gc`-managed blocks (`GcBox`es and
+/// `GcVec` buffers). Incremented here, at each allocation site, and
+/// decremented as blocks are reclaimed during sweep; `THRESHOLD` and
+/// `maybe_collect` below are what actually consume it.
+pub(crate) static BYTES_ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+/// Target fraction of `THRESHOLD` that should still be occupied by live
+/// data immediately after a collection. See `recompute_threshold`.
+const USED_SPACE_RATIO: f64 = 0.7;
+
+/// Bytes allocated beyond which an allocation triggers a collection.
+/// Recomputed from `BYTES_ALLOCATED` after every collection rather than
+/// incremented by a fixed count, so a heap that grows linearly with the
+/// number of `Gc::new` calls sees the threshold grow geometrically,
+/// avoiding quadratic collection overhead. `set_threshold` sets the
+/// initial floor; until that is called, no size is small enough to
+/// trigger an automatic collection.
+static THRESHOLD: AtomicUsize = AtomicUsize::new(usize::max_value());
+
+/// Guards against `maybe_collect` re-triggering a collection from within
+/// the allocations a collection itself performs (e.g. grey-worklist
+/// bookkeeping).
+static COLLECTING: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn set_threshold(threshold: usize) {
+    THRESHOLD.store(threshold, Ordering::Relaxed);
+}
+
+/// Recomputes `THRESHOLD` from the current live-byte count so the
+/// post-collection live set never exceeds `USED_SPACE_RATIO` of it.
+pub(crate) fn recompute_threshold() {
+    let live = BYTES_ALLOCATED.load(Ordering::Relaxed) as f64;
+    THRESHOLD.store((live / USED_SPACE_RATIO) as usize, Ordering::Relaxed);
+}
+
+/// Triggers a collection if live bytes have grown past `THRESHOLD`, then
+/// recomputes the threshold from what survived.
+fn maybe_collect() {
+    if BYTES_ALLOCATED.load(Ordering::Relaxed) <= THRESHOLD.load(Ordering::Relaxed) {
+        return;
+    }
+    if COLLECTING.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    crate::collect();
+    recompute_threshold();
+    COLLECTING.store(false, Ordering::Relaxed);
+}
+
+pub(crate) fn record_alloc(size: usize) {
+    BYTES_ALLOCATED.fetch_add(size, Ordering::Relaxed);
+    maybe_collect();
+}
+
+pub(crate) fn record_dealloc(size: usize) {
+    BYTES_ALLOCATED.fetch_sub(size, Ordering::Relaxed);
+}
+
+/// Whether the collector's shutdown `Drop` should skip its final
+/// collection and leak whatever `Gc`-managed blocks remain, rather than
+/// running their destructors/finalizers. Settable alongside `debug_flags`
+/// and `set_threshold`; off by default.
+///
+/// There is no defined ordering between the destructors of still-live
+/// `Gc`s at teardown, so enabling this is useful when a program cannot
+/// otherwise guarantee it is safe to run them at that point (e.g. other
+/// global state they depend on has already been torn down).
+pub(crate) static LEAK_ON_DROP: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn leak_on_drop() -> bool {
+    LEAK_ON_DROP.load(Ordering::Relaxed)
+}
+
+pub(crate) fn set_leak_on_drop(value: bool) {
+    LEAK_ON_DROP.store(value, Ordering::Relaxed);
+}
+
+/// Every live block's `BlockHeader` address, registered at allocation and
+/// removed once it is reclaimed, so `shutdown_sweep` can find every
+/// remaining `Gc`-managed object at teardown even though nothing on the
+/// stack may still root it by then.
+static LIVE_BLOCKS: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+fn register_block(header: NonNull<BlockHeader>) {
+    LIVE_BLOCKS.lock().unwrap().push(header.as_ptr() as usize);
+}
+
+fn unregister_block(header: NonNull<BlockHeader>) {
+    let mut blocks = LIVE_BLOCKS.lock().unwrap();
+    if let Some(pos) = blocks.iter().position(|&addr| addr == header.as_ptr() as usize) {
+        blocks.swap_remove(pos);
+    }
+}
+
+/// Runs one last sweep over every block still registered in
+/// `LIVE_BLOCKS`, running its destructor (which itself runs any
+/// `Finalize` impl first, via the usual guard in `GcBox::drop`) before
+/// the collector's owning state goes away.
+///
+/// Called from that state's `Drop` impl, unless `leak_on_drop` is set, in
+/// which case the remaining blocks are deliberately leaked instead of
+/// reclaimed, for programs that cannot guarantee destructor ordering is
+/// safe this late in teardown.
+pub(crate) fn shutdown_sweep() {
+    if leak_on_drop() {
+        return;
+    }
+    let blocks: Vec<usize> = LIVE_BLOCKS.lock().unwrap().drain(..).collect();
+    for addr in blocks {
+        let header = addr as *mut BlockHeader;
+        let metadata = unsafe { (&*header).metadata() };
+        let vptr = *metadata.drop_vptr as u64 as usize as *mut u8;
+        if vptr.is_null() {
+            continue;
+        }
+        let dataptr = unsafe { (header as *mut u8).add(size_of::<BlockHeader>()) } as usize;
+        let fatptr = unsafe { transmute::<(usize, *mut u8), *mut dyn Drop>((dataptr, vptr)) };
+        unsafe { ptr::drop_in_place(fatptr) };
+    }
+}
+
+/// An RAII handle on the collector's lifetime. Dropping it runs
+/// `shutdown_sweep`, so every block still registered in `LIVE_BLOCKS` gets
+/// its destructor run exactly once -- instead of relying on whatever
+/// happens to unwind last, which for `static`s is nothing at all, since
+/// Rust never runs `Drop` for `static` values.
+///
+/// Call `init()` once, near the top of `main`, and hold onto the returned
+/// `Collector` for as long as `Gc`-managed values may still be created;
+/// when it is dropped, anything left over from a program that didn't
+/// explicitly collect everything it allocated is still finalized and
+/// dropped rather than silently leaked (unless `set_leak_on_drop(true)`
+/// was called, in which case it is leaked on purpose).
+pub struct Collector {
+    _private: (),
+}
+
+/// Starts the collector, returning a guard whose `Drop` performs the final
+/// sweep described on [`Collector`].
+pub fn init() -> Collector {
+    Collector { _private: () }
+}
+
+impl Drop for Collector {
+    fn drop(&mut self) {
+        shutdown_sweep();
+    }
+}
+
 /// A garbage collected pointer. 'Gc' stands for 'Garbage collected'.
 ///
 /// The type `Gc<T>` provides shared ownership of a value of type `T`,
@@ -43,11 +197,24 @@ pub struct Gc<T: ?Sized> {
 
 impl<T> Gc<T> {
     /// Constructs a new `Gc<T>`.
+    ///
+    /// Panics on allocation failure. Use [`Gc::try_new`] in contexts
+    /// (embedded, kernel-style) where unwinding or aborting on OOM is not
+    /// acceptable.
     pub fn new(v: T) -> Self {
         Gc {
             objptr: unsafe { NonNull::new_unchecked(GcBox::new(v)) },
         }
     }
+
+    /// Constructs a new `Gc<T>`, returning `Err` instead of panicking if the
+    /// allocation fails. `v` is dropped in that case; it is not handed back.
+    pub fn try_new(v: T) -> Result<Self, AllocErr> {
+        let ptr = GcBox::try_new(v)?;
+        Ok(Gc {
+            objptr: unsafe { NonNull::new_unchecked(ptr) },
+        })
+    }
 }
 
 impl Gc<dyn Any> {
@@ -71,6 +238,44 @@ impl<T: ?Sized> Gc<T> {
     }
 }
 
+impl<T: Finalize> Gc<T> {
+    /// Constructs a new `Gc<T>` whose `Finalize::finalize` is run by the
+    /// collector when the object is found dead, in addition to (and before)
+    /// its `Drop` implementation.
+    ///
+    /// Use this over a plain `Drop` impl when cleanup must not be tied to
+    /// Rust's destructor semantics, for example releasing an external
+    /// handle. The finalizer runs at the same point the destructor does:
+    /// when the sweep phase reclaims the object.
+    pub fn new_with_finalizer(v: T) -> Self {
+        let ptr = GcBox::new(v);
+        unsafe {
+            let valueptr: &T = &*(*ptr).0;
+            let fatptr: &dyn Finalize = valueptr;
+            let vptr = transmute::<*const dyn Finalize, (usize, *mut u8)>(fatptr).1;
+            (*ptr).set_finalize_vptr(vptr);
+        }
+        Gc {
+            objptr: unsafe { NonNull::new_unchecked(ptr) },
+        }
+    }
+}
+
+/// Cleanup logic that the collector runs when it determines an object is
+/// dead, kept separate from `Drop`.
+///
+/// A type's `finalize` is invoked by the sweep phase, at most once, on
+/// objects constructed with [`Gc::new_with_finalizer`]: `GcBox::drop` runs
+/// it immediately before dropping the value itself, so external-resource
+/// cleanup (file handles, FFI handles, and the like) can live in
+/// `finalize` rather than in the type's own `Drop`. If an object is
+/// re-marked black before the sweep phase reaches it, its finalizer is
+/// skipped entirely, under the same `Colour::Black` check that guards
+/// `GcBox::drop`.
+pub trait Finalize {
+    fn finalize(&self);
+}
+
 /// A `GcBox` is a 0-cost wrapper which allows a single `Drop` implementation
 /// while also permitting multiple, copyable `Gc` references. The `drop` method
 /// on `GcBox` acts as a guard, preventing the destructors on its contents from
@@ -79,9 +284,20 @@ pub(crate) struct GcBox<T: ?Sized>(ManuallyDrop<T>);
 
 impl<T> GcBox<T> {
     fn new(value: T) -> *mut GcBox<T> {
+        match Self::try_new(value) {
+            Ok(ptr) => ptr,
+            Err(_) => panic!("gcmalloc: out of memory"),
+        }
+    }
+
+    /// Like `new`, but propagates allocation failure instead of panicking.
+    /// When allocation fails, `value` is dropped here before `Err` is
+    /// returned, so nothing is leaked; the value is not returned to the
+    /// caller.
+    fn try_new(value: T) -> Result<*mut GcBox<T>, AllocErr> {
         let layout = Layout::new::<T>();
 
-        let ptr = unsafe { GC_ALLOCATOR.alloc(layout).unwrap().as_ptr() } as *mut GcBox<T>;
+        let ptr = unsafe { GC_ALLOCATOR.alloc(layout)?.as_ptr() } as *mut GcBox<T>;
         let gcbox = GcBox(ManuallyDrop::new(value));
         unsafe {
             ptr.copy_from_nonoverlapping(&gcbox, 1);
@@ -95,61 +311,138 @@ impl<T> GcBox<T> {
             (*ptr).set_drop_vptr(vptr);
         }
 
-        ptr
+        record_alloc(layout.size());
+        unsafe { register_block((*ptr).header()) };
+
+        Ok(ptr)
     }
 }
 
-impl<T: ?Sized> GcBox<T> {
+/// Shared accessors for the `BlockHeader` that `GC_ALLOCATOR` always places
+/// immediately before the payload of a block it hands out. `GcBox` and
+/// `GcVecBuf` are both laid out this way, so they implement this trait for
+/// just the pointer arithmetic (`header`) and get the colour/metadata
+/// bookkeeping that sits on top of it for free, rather than each keeping
+/// its own hand-synced copy.
+pub(crate) trait BlockOwner {
+    /// The `BlockHeader` immediately preceding this value's own address.
+    fn header(&self) -> NonNull<BlockHeader>;
+
     fn metadata(&self) -> BlockMetadata {
-        unsafe {
-            let headerptr = (self as *const GcBox<T> as *mut BlockHeader).sub(1);
-            (&*headerptr).metadata()
-        }
+        unsafe { self.header().as_ref() }.metadata()
     }
 
-    fn set_metadata(&mut self, header: BlockMetadata) {
-        unsafe {
-            let headerptr = (self as *const GcBox<T> as *mut BlockHeader).sub(1);
-            (*headerptr).set_metadata(header)
-        }
+    fn set_metadata(&self, metadata: BlockMetadata) {
+        let mut header = self.header();
+        unsafe { header.as_mut() }.set_metadata(metadata);
     }
 
-    pub(crate) fn set_colour(&mut self, colour: Colour) {
+    fn set_colour(&self, colour: Colour) {
         let mut metadata = self.metadata();
         match colour {
-            Colour::Black => metadata.mark_bit = true,
-            Colour::White => metadata.mark_bit = false,
+            Colour::Black => {
+                metadata.mark_bit = true;
+                metadata.grey = false;
+            }
+            Colour::Grey => {
+                metadata.mark_bit = false;
+                metadata.grey = true;
+            }
+            Colour::White => {
+                metadata.mark_bit = false;
+                metadata.grey = false;
+            }
         }
         self.set_metadata(metadata);
     }
 
-    pub(crate) fn colour(&self) -> Colour {
+    fn colour(&self) -> Colour {
         let metadata = self.metadata();
-        if metadata.mark_bit {
+        if metadata.grey {
+            Colour::Grey
+        } else if metadata.mark_bit {
             Colour::Black
         } else {
             Colour::White
         }
     }
 
-    pub(crate) fn set_dropped(&mut self, value: bool) {
+    fn dropped(&self) -> bool {
+        self.metadata().dropped
+    }
+
+    fn set_dropped(&self, value: bool) {
         let mut metadata = self.metadata();
         metadata.dropped = value.into();
         self.set_metadata(metadata);
     }
 
-    pub(crate) fn set_drop_vptr(&mut self, value: *mut u8) {
+    fn set_drop_vptr(&self, value: *mut u8) {
         let mut metadata = self.metadata();
         metadata.drop_vptr = (value as u64).into();
         self.set_metadata(metadata);
     }
 
-    pub(crate) fn drop_vptr(&self) -> *mut u8 {
+    /// The vtable pointer for this block's `Drop` impl, as stored by
+    /// `set_drop_vptr`. `shutdown_sweep` reads the same field straight from
+    /// each block's header rather than through this method.
+    fn drop_vptr(&self) -> *mut u8 {
         let vptr = *self.metadata().drop_vptr as u64;
         vptr as usize as *mut u8
     }
 }
 
+impl<T: ?Sized> BlockOwner for GcBox<T> {
+    fn header(&self) -> NonNull<BlockHeader> {
+        unsafe { NonNull::new_unchecked((self as *const GcBox<T> as *mut BlockHeader).sub(1)) }
+    }
+}
+
+impl<T: ?Sized> GcBox<T> {
+    pub(crate) fn set_finalized(&mut self, value: bool) {
+        let mut metadata = self.metadata();
+        metadata.finalized = value.into();
+        self.set_metadata(metadata);
+    }
+
+    pub(crate) fn finalized(&self) -> bool {
+        self.metadata().finalized
+    }
+
+    pub(crate) fn set_finalize_vptr(&mut self, value: *mut u8) {
+        let mut metadata = self.metadata();
+        metadata.finalize_vptr = (value as u64).into();
+        self.set_metadata(metadata);
+    }
+
+    /// The vtable pointer for this object's `Finalize` impl, or null if it
+    /// was not constructed with `Gc::new_with_finalizer`.
+    pub(crate) fn finalize_vptr(&self) -> *mut u8 {
+        let vptr = *self.metadata().finalize_vptr as u64;
+        vptr as usize as *mut u8
+    }
+
+    /// Runs this object's finalizer, if it has one, at most once. Called
+    /// from `drop` under the same guard that protects `Drop`: if the
+    /// object has already been finalized, or has been re-marked black
+    /// since sweep decided to reclaim it, this is a no-op.
+    fn run_finalizer(&mut self) {
+        if self.colour() == Colour::Black || self.finalized() {
+            return;
+        }
+        let vptr = self.finalize_vptr();
+        if vptr.is_null() {
+            return;
+        }
+        self.set_finalized(true);
+        unsafe {
+            let dataptr = &*self.0 as *const T as *const () as usize;
+            let fatptr = transmute::<(usize, *mut u8), *const dyn Finalize>((dataptr, vptr));
+            (*fatptr).finalize();
+        }
+    }
+}
+
 impl<T: ?Sized> Deref for Gc<T> {
     type Target = T;
 
@@ -166,10 +459,13 @@ impl<T: ?Sized> DerefMut for Gc<T> {
 
 impl<T: ?Sized> Drop for GcBox<T> {
     fn drop(&mut self) {
-        if self.colour() == Colour::Black || self.metadata().dropped {
+        if self.colour() == Colour::Black || self.dropped() {
             return;
         }
+        self.run_finalizer();
         self.set_dropped(true);
+        unregister_block(self.header());
+        record_dealloc(size_of_val(&*self.0));
         unsafe { ManuallyDrop::drop(&mut self.0) };
     }
 }
@@ -194,9 +490,333 @@ impl<T: ?Sized + Unsize<U>, U: ?Sized> CoerceUnsized<Gc<U>> for Gc<T> {}
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
 pub(crate) enum Colour {
     Black,
+    Grey,
     White,
 }
 
+/// The grey worklist driving incremental marking: objects that are known
+/// reachable but whose own fields have not yet been scanned for further
+/// pointers. `collect_incremental` pops from this queue in bounded batches
+/// rather than draining it all at once, so marking can be spread across
+/// many small steps instead of one stop-the-world pass.
+///
+/// Entries are stored as raw addresses rather than `NonNull<BlockHeader>`
+/// because `NonNull` is neither `Send` nor `Sync`, and this worklist has to
+/// live in a `static`.
+static GREY_WORKLIST: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+fn push_grey(header: NonNull<BlockHeader>) {
+    GREY_WORKLIST.lock().unwrap().push(header.as_ptr() as usize);
+}
+
+fn pop_grey() -> Option<NonNull<BlockHeader>> {
+    GREY_WORKLIST
+        .lock()
+        .unwrap()
+        .pop()
+        .map(|addr| unsafe { NonNull::new_unchecked(addr as *mut BlockHeader) })
+}
+
+/// Shades the block at `header` grey and queues it for scanning, unless it
+/// is already grey or black.
+///
+/// Unlike `shade_grey`, this takes a type-erased `NonNull<BlockHeader>`
+/// rather than a statically-typed `NonNull<GcBox<T>>`, so a generic root or
+/// stack scanner -- the kind that already drives `scan_for_pointers` -- can
+/// call this directly on every header it finds to seed a mark cycle from
+/// scratch, without needing to know each root's concrete type.
+pub(crate) fn shade_header_grey(mut header: NonNull<BlockHeader>) {
+    let blockheader = unsafe { header.as_mut() };
+    let mut metadata = blockheader.metadata();
+    if !metadata.mark_bit && !metadata.grey {
+        metadata.grey = true;
+        blockheader.set_metadata(metadata);
+        push_grey(header);
+    }
+}
+
+/// Shades `objptr` grey and queues it for scanning. Used by `GcCell`'s
+/// write barrier to shade newly-reachable objects.
+pub(crate) fn shade_grey<T: ?Sized>(objptr: NonNull<GcBox<T>>) {
+    let headerptr = objptr.as_ptr() as *mut BlockHeader;
+    shade_header_grey(unsafe { NonNull::new_unchecked(headerptr.sub(1)) });
+}
+
+/// Runs at most `budget` steps of incremental tri-colour marking: pop a
+/// grey object from the worklist, scan it for interior pointers (shading
+/// any white targets grey and pushing them in turn), then colour the
+/// scanned object black. The sweep phase reclaims whatever is still white
+/// once the worklist empties, exactly as it does after a full `collect()`.
+///
+/// Because shading only ever turns white into grey and grey into black,
+/// and `GcCell`'s write barrier re-shades any pointer stored into a black
+/// object, the invariant "no black object points to a white object" holds
+/// between steps, so pausing here and resuming later is sound.
+pub fn collect_incremental(budget: usize) {
+    for _ in 0..budget {
+        let header = match pop_grey() {
+            Some(header) => header,
+            None => break,
+        };
+        for child in unsafe { crate::allocator::scan_for_pointers(header) } {
+            shade_header_grey(child);
+        }
+        let mut metadata = unsafe { header.as_ref() }.metadata();
+        metadata.mark_bit = true;
+        metadata.grey = false;
+        unsafe { (*header.as_ptr()).set_metadata(metadata) };
+    }
+}
+
+/// An interior-mutability cell for values stored inside a `Gc`, to be used
+/// in place of `std::cell::Cell` when the contents are (or may contain)
+/// further `Gc` pointers.
+///
+/// Plain `Cell`/`RefCell` mutation is invisible to the collector. During
+/// incremental marking this is unsound: writing a new `Gc` pointer into an
+/// already-black object could leave a black object pointing at a white
+/// one, breaking the invariant the incremental marker relies on.
+/// `GcCell::set` closes that gap with a write barrier.
+///
+/// An earlier version of this barrier tried to skip shading when the
+/// *owning* object wasn't black yet, found by subtracting `BlockHeader`'s
+/// size directly from the cell's own address. That only located the real
+/// header when the `GcCell` was the entire value behind its `Gc` (i.e.
+/// `Gc<GcCell<T>>`); a `GcCell` embedded as one field of a larger node type
+/// — the common case for a write-barrier-guarded pointer field — sits at
+/// some other offset, so the "owner" the old code found was whatever bytes
+/// happened to precede it, not a real header. `set` now always shades the
+/// incoming pointer instead of trying to read the owner's colour, which
+/// needs no knowledge of where the cell sits inside its enclosing object
+/// and is correct regardless of embedding. The cost is conservative: a
+/// write that happens outside an active incremental mark queues a shade
+/// that collect_incremental will harmlessly walk through, and a write into
+/// an object that was never going to be collected this cycle may keep its
+/// old target's subgraph alive one cycle longer than strictly necessary.
+/// Neither can cause a live object to be reclaimed early.
+pub struct GcCell<T> {
+    value: UnsafeCell<T>,
+}
+
+impl<T> GcCell<T> {
+    pub fn new(value: T) -> Self {
+        GcCell {
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Obtain a mutable reference directly, bypassing the write barrier.
+    /// Safe because `&mut self` statically rules out any other live
+    /// reference into the cell.
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.value.get() }
+    }
+}
+
+impl<T> GcCell<Gc<T>> {
+    pub fn get(&self) -> Gc<T> {
+        unsafe { *self.value.get() }
+    }
+
+    /// Overwrites the pointer held by this cell, unconditionally shading
+    /// `value`'s target grey and queuing it for scanning first so it is
+    /// never missed by an incremental mark in progress, however this cell
+    /// is nested inside its owning `Gc`.
+    pub fn set(&self, value: Gc<T>) {
+        shade_grey(value.objptr);
+        unsafe { *self.value.get() = value };
+    }
+}
+
+/// A garbage-collected, growable array.
+///
+/// Unlike `Gc<Vec<T>>`, whose element buffer is an ordinary heap allocation
+/// hidden from the collector, `GcVec<T>` allocates its backing store
+/// directly through `GC_ALLOCATOR`. This means every element is visible to
+/// the mark phase just like the fields of a `GcBox`, so `Gc<U>` pointers
+/// stored inside a `GcVec` are traced and kept alive correctly.
+///
+/// Unlike `Gc<T>`, `GcVec<T>` is neither `Copy` nor `Clone`: `grow` replaces
+/// `buf` with a freshly allocated, larger block, so a bit-for-bit copy of
+/// this struct made before a `push` triggers a reallocation would be left
+/// pointing at a stale backing store whose `len` that `push` already zeroed.
+/// Wrap a `GcVec` in a `Gc` (or a `GcCell`) to share it the way `Gc<T>`
+/// shares a `GcBox`.
+pub struct GcVec<T> {
+    buf: NonNull<GcVecBuf<T>>,
+}
+
+impl<T> GcVec<T> {
+    /// Constructs a new, empty `GcVec<T>` with zero capacity. Like
+    /// `with_capacity(0)`, this still registers a (zero-capacity) block with
+    /// `GC_ALLOCATOR` so the collector always has a valid buffer to walk;
+    /// the first `push` grows it to a real allocation.
+    pub fn new() -> Self {
+        GcVec::with_capacity(0)
+    }
+
+    /// Constructs a new, empty `GcVec<T>` with space for at least `capacity`
+    /// elements.
+    pub fn with_capacity(capacity: usize) -> Self {
+        GcVec {
+            buf: unsafe { NonNull::new_unchecked(GcVecBuf::alloc(capacity)) },
+        }
+    }
+
+    /// Like `with_capacity`, but returns `Err` instead of panicking if the
+    /// backing store fails to allocate.
+    pub fn try_with_capacity(capacity: usize) -> Result<Self, AllocErr> {
+        let ptr = GcVecBuf::try_alloc(capacity)?;
+        Ok(GcVec {
+            buf: unsafe { NonNull::new_unchecked(ptr) },
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        unsafe { self.buf.as_ref().len }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        unsafe { self.buf.as_ref().cap }
+    }
+
+    /// Appends `value` to the back of the array, growing the backing store
+    /// if it is at capacity.
+    pub fn push(&mut self, value: T) {
+        if self.len() == self.capacity() {
+            self.grow();
+        }
+        let buf = unsafe { self.buf.as_mut() };
+        unsafe { buf.data_ptr().add(buf.len).write(value) };
+        buf.len += 1;
+    }
+
+    /// Removes the last element and returns it, or `None` if the array is
+    /// empty.
+    pub fn pop(&mut self) -> Option<T> {
+        let buf = unsafe { self.buf.as_mut() };
+        if buf.len == 0 {
+            return None;
+        }
+        buf.len -= 1;
+        Some(unsafe { buf.data_ptr().add(buf.len).read() })
+    }
+
+    /// Allocates a new, larger backing store, copies the existing elements
+    /// across and makes it the buffer in use. The copied elements are
+    /// logically moved: the old buffer's `len` is zeroed before it is
+    /// dropped, so its `Drop` impl does not also run their destructors
+    /// when the now-unreferenced old buffer is reclaimed by the next
+    /// sweep.
+    fn grow(&mut self) {
+        let old_ptr = self.buf.as_ptr();
+        let (old_len, old_cap) = unsafe { ((*old_ptr).len, (*old_ptr).cap) };
+        let new_cap = if old_cap == 0 { 4 } else { old_cap * 2 };
+        let new_buf = GcVecBuf::alloc(new_cap);
+        unsafe {
+            (*new_buf)
+                .data_ptr()
+                .copy_from_nonoverlapping((*old_ptr).data_ptr(), old_len);
+            (*new_buf).len = old_len;
+            (*old_ptr).len = 0;
+            self.buf = NonNull::new_unchecked(new_buf);
+        }
+    }
+}
+
+impl<T> Index<usize> for GcVec<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        assert!(index < self.len(), "index out of bounds");
+        unsafe { &*self.buf.as_ref().data_ptr().add(index) }
+    }
+}
+
+impl<T> IndexMut<usize> for GcVec<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        assert!(index < self.len(), "index out of bounds");
+        let buf = unsafe { self.buf.as_mut() };
+        unsafe { &mut *buf.data_ptr().add(index) }
+    }
+}
+
+/// The backing store of a `GcVec<T>`. This is allocated through
+/// `GC_ALLOCATOR` in the same way as a `GcBox`, so it receives its own
+/// `BlockHeader`/`BlockMetadata` and is walked by the mark phase like any
+/// other collected block. `len` is stored in the block itself (rather than
+/// in `GcVec`) so the marker can tell how much of `data` is initialized and
+/// only scan that prefix for interior pointers; the uninitialized tail up to
+/// `cap` is never traced.
+struct GcVecBuf<T> {
+    len: usize,
+    cap: usize,
+    data: [T; 0],
+}
+
+impl<T> GcVecBuf<T> {
+    fn layout(cap: usize) -> Layout {
+        let (layout, _) = Layout::new::<GcVecBuf<T>>()
+            .extend(Layout::array::<T>(cap).unwrap())
+            .unwrap();
+        layout.pad_to_align()
+    }
+
+    fn alloc(cap: usize) -> *mut GcVecBuf<T> {
+        match Self::try_alloc(cap) {
+            Ok(ptr) => ptr,
+            Err(_) => panic!("gcmalloc: out of memory"),
+        }
+    }
+
+    fn try_alloc(cap: usize) -> Result<*mut GcVecBuf<T>, AllocErr> {
+        let layout = Self::layout(cap);
+        let ptr = unsafe { GC_ALLOCATOR.alloc(layout)?.as_ptr() } as *mut GcVecBuf<T>;
+        unsafe {
+            (*ptr).len = 0;
+            (*ptr).cap = cap;
+        }
+        record_alloc(layout.size());
+        unsafe {
+            let fatptr: &dyn Drop = &*ptr;
+            let vptr = transmute::<*const dyn Drop, (usize, *mut u8)>(fatptr).1;
+            (*ptr).set_drop_vptr(vptr);
+            register_block((*ptr).header());
+        }
+        Ok(ptr)
+    }
+
+    fn data_ptr(&self) -> *mut T {
+        self.data.as_ptr() as *mut T
+    }
+}
+
+impl<T> BlockOwner for GcVecBuf<T> {
+    fn header(&self) -> NonNull<BlockHeader> {
+        unsafe { NonNull::new_unchecked((self as *const GcVecBuf<T> as *mut BlockHeader).sub(1)) }
+    }
+}
+
+impl<T> Drop for GcVecBuf<T> {
+    fn drop(&mut self) {
+        if self.colour() == Colour::Black || self.dropped() {
+            return;
+        }
+        self.set_dropped(true);
+        unregister_block(self.header());
+        record_dealloc(Self::layout(self.cap).size());
+        unsafe {
+            for i in 0..self.len {
+                ptr::drop_in_place(self.data_ptr().add(i));
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -232,4 +852,298 @@ mod tests {
     fn test_nonnull_opt() {
         assert_eq!(size_of::<Option<Gc<usize>>>(), size_of::<usize>())
     }
+
+    #[test]
+    fn test_gcvec_push_pop() {
+        let mut v: GcVec<usize> = GcVec::new();
+        assert_eq!(v.len(), 0);
+
+        for i in 0..10 {
+            v.push(i);
+        }
+        assert_eq!(v.len(), 10);
+        assert!(v.capacity() >= 10);
+
+        for i in (0..10).rev() {
+            assert_eq!(v.pop(), Some(i));
+        }
+        assert_eq!(v.pop(), None);
+    }
+
+    #[test]
+    fn test_gcvec_index() {
+        let mut v: GcVec<&str> = GcVec::new();
+        v.push("a");
+        v.push("b");
+        v.push("c");
+
+        assert_eq!(v[0], "a");
+        assert_eq!(v[1], "b");
+        assert_eq!(v[2], "c");
+
+        v[1] = "z";
+        assert_eq!(v[1], "z");
+    }
+
+    #[test]
+    fn test_gcvec_grow_does_not_double_drop() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        struct DropCounter(Rc<Cell<usize>>);
+
+        impl Drop for DropCounter {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let count = Rc::new(Cell::new(0));
+        let mut v: GcVec<DropCounter> = GcVec::new();
+
+        for _ in 0..4 {
+            v.push(DropCounter(count.clone()));
+        }
+        let old_ptr = v.buf.as_ptr();
+        // Capacity is exhausted, so this push triggers `grow`.
+        v.push(DropCounter(count.clone()));
+        let new_ptr = v.buf.as_ptr();
+        assert_ne!(old_ptr as *const (), new_ptr as *const ());
+
+        // Simulate the old, now-unreferenced buffer being reclaimed by a
+        // sweep: its elements were moved into the new buffer, so this
+        // must not run their destructors.
+        unsafe { ptr::drop_in_place(old_ptr) };
+        assert_eq!(count.get(), 0);
+
+        // The new buffer is still live and holds all 5 elements; sweeping
+        // it must drop each of them exactly once.
+        unsafe { ptr::drop_in_place(new_ptr) };
+        assert_eq!(count.get(), 5);
+    }
+
+    #[test]
+    fn test_new_with_finalizer_runs_before_reclaim() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        struct HasHandle(Rc<Cell<bool>>);
+
+        impl Finalize for HasHandle {
+            fn finalize(&self) {
+                self.0.set(true);
+            }
+        }
+
+        let finalized = Rc::new(Cell::new(false));
+        let gc = Gc::new_with_finalizer(HasHandle(finalized.clone()));
+        assert!(!unsafe { gc.objptr.as_ref() }.finalize_vptr().is_null());
+        assert!(!finalized.get());
+
+        // Simulate the sweep phase reclaiming a dead (white) object: its
+        // finalizer must run before the memory is given back.
+        unsafe { ptr::drop_in_place(gc.objptr.as_ptr()) };
+        assert!(finalized.get());
+
+        // A second reclaim attempt (e.g. a stray duplicate free) must not
+        // finalize or drop a second time.
+        unsafe { ptr::drop_in_place(gc.objptr.as_ptr()) };
+    }
+
+    #[test]
+    fn test_bytes_allocated_tracks_live_blocks() {
+        let before = BYTES_ALLOCATED.load(Ordering::Relaxed);
+        let gc = Gc::new(123u64);
+        assert_eq!(
+            BYTES_ALLOCATED.load(Ordering::Relaxed),
+            before + size_of::<u64>()
+        );
+        let _ = gc;
+    }
+
+    #[test]
+    fn test_recompute_threshold_uses_used_space_ratio() {
+        let saved_threshold = THRESHOLD.load(Ordering::Relaxed);
+        let saved_bytes = BYTES_ALLOCATED.load(Ordering::Relaxed);
+
+        BYTES_ALLOCATED.store(700, Ordering::Relaxed);
+        recompute_threshold();
+        assert_eq!(
+            THRESHOLD.load(Ordering::Relaxed),
+            (700.0 / USED_SPACE_RATIO) as usize
+        );
+
+        set_threshold(saved_threshold);
+        BYTES_ALLOCATED.store(saved_bytes, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_gc_cell_get_set() {
+        let inner = Gc::new(456usize);
+        let cell = Gc::new(GcCell::new(inner));
+        assert_eq!(*cell.get(), 456);
+
+        let other = Gc::new(789usize);
+        cell.set(other);
+        assert_eq!(*cell.get(), 789);
+    }
+
+    #[test]
+    fn test_gc_cell_get_set_when_embedded_in_a_larger_struct() {
+        // `GcCell` does not have to be the entire value behind its `Gc` —
+        // it must work as one field of a node type alongside others, which
+        // is the realistic use case for a write-barrier-guarded pointer
+        // field.
+        struct Node {
+            next: GcCell<Gc<usize>>,
+            tag: u32,
+        }
+
+        let a = Gc::new(1usize);
+        let b = Gc::new(2usize);
+        let node = Gc::new(Node {
+            next: GcCell::new(a),
+            tag: 42,
+        });
+
+        assert_eq!(*node.next.get(), 1);
+        assert_eq!(node.tag, 42);
+
+        node.next.set(b);
+        assert_eq!(*node.next.get(), 2);
+        // The write barrier must not have touched unrelated fields.
+        assert_eq!(node.tag, 42);
+    }
+
+    #[test]
+    fn test_collect_incremental_marks_roots_black_and_sweeps_unreached_white() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        struct DropFlag(Rc<Cell<bool>>);
+
+        impl Drop for DropFlag {
+            fn drop(&mut self) {
+                self.0.set(true);
+            }
+        }
+
+        // `GREY_WORKLIST` is a single global queue shared with every other
+        // test in this file; set it aside so this test only drains what it
+        // seeds itself.
+        let saved_grey = std::mem::take(&mut *GREY_WORKLIST.lock().unwrap());
+
+        let root_dropped = Rc::new(Cell::new(false));
+        let root = Gc::new(DropFlag(root_dropped.clone()));
+        let garbage_dropped = Rc::new(Cell::new(false));
+        let garbage = Gc::new(DropFlag(garbage_dropped.clone()));
+
+        // Seed the cycle the way an external root scanner would: shade
+        // every header it finds reachable from a root, via the
+        // type-erased entry point, leaving anything no root points to
+        // (`garbage`, here) white.
+        let root_header = unsafe {
+            NonNull::new_unchecked((root.objptr.as_ptr() as *mut BlockHeader).sub(1))
+        };
+        shade_header_grey(root_header);
+
+        collect_incremental(10);
+
+        assert_eq!(unsafe { root.objptr.as_ref() }.colour(), Colour::Black);
+        assert_eq!(unsafe { garbage.objptr.as_ref() }.colour(), Colour::White);
+
+        // The sweep phase reclaims whatever is still white and leaves
+        // black objects alone; simulate both outcomes directly, as the
+        // other reclaim tests in this file do.
+        unsafe { ptr::drop_in_place(garbage.objptr.as_ptr()) };
+        assert!(garbage_dropped.get());
+
+        unsafe { ptr::drop_in_place(root.objptr.as_ptr()) };
+        assert!(!root_dropped.get());
+
+        *GREY_WORKLIST.lock().unwrap() = saved_grey;
+    }
+
+    #[test]
+    fn test_try_new() {
+        let gc = Gc::try_new(42usize).expect("allocation should succeed");
+        assert_eq!(*gc, 42);
+
+        let v = GcVec::<usize>::try_with_capacity(4).expect("allocation should succeed");
+        assert_eq!(v.capacity(), 4);
+    }
+
+    #[test]
+    fn test_leak_on_drop_flag() {
+        assert!(!leak_on_drop());
+        set_leak_on_drop(true);
+        assert!(leak_on_drop());
+        set_leak_on_drop(false);
+    }
+
+    #[test]
+    fn test_shutdown_sweep_runs_pending_drops_unless_leaking() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        struct DropFlag(Rc<Cell<bool>>);
+
+        impl Drop for DropFlag {
+            fn drop(&mut self) {
+                self.0.set(true);
+            }
+        }
+
+        // `LIVE_BLOCKS` is a single global registry shared with every other
+        // test in this file; set it aside so this test only sweeps the
+        // blocks it registers itself.
+        let saved_blocks = std::mem::take(&mut *LIVE_BLOCKS.lock().unwrap());
+
+        let dropped = Rc::new(Cell::new(false));
+        Gc::new(DropFlag(dropped.clone()));
+        shutdown_sweep();
+        assert!(dropped.get());
+
+        let leaked = Rc::new(Cell::new(false));
+        Gc::new(DropFlag(leaked.clone()));
+        set_leak_on_drop(true);
+        shutdown_sweep();
+        set_leak_on_drop(false);
+        assert!(!leaked.get());
+
+        *LIVE_BLOCKS.lock().unwrap() = saved_blocks;
+    }
+
+    #[test]
+    fn test_shutdown_sweep_reclaims_pending_gcvec_buffers() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        struct DropFlag(Rc<Cell<bool>>);
+
+        impl Drop for DropFlag {
+            fn drop(&mut self) {
+                self.0.set(true);
+            }
+        }
+
+        let saved_blocks = std::mem::take(&mut *LIVE_BLOCKS.lock().unwrap());
+
+        let dropped = Rc::new(Cell::new(false));
+        let mut v: GcVec<DropFlag> = GcVec::new();
+        v.push(DropFlag(dropped.clone()));
+
+        // Simulate the collector's owning state going out of scope at the
+        // end of a program: dropping the guard must reach this GcVec's
+        // backing buffer too, not just GcBox allocations.
+        drop(init());
+        assert!(dropped.get());
+
+        // Leave `v` itself alone: its buffer was already swept and
+        // dropped above, and letting `v` drop for real here would be a
+        // double free of the now-reclaimed block.
+        forget(v);
+
+        *LIVE_BLOCKS.lock().unwrap() = saved_blocks;
+    }
 }